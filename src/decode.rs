@@ -0,0 +1,139 @@
+//! Decoders that invert the BEN/XBEN encoders in [`crate::encode`].
+//!
+//! [`decode_xben_to_ben`] reads the plaintext header an XBEN file starts
+//! with, dispatches to the matching decompressor, and repacks the
+//! resulting ben32 run stream into the bit-packed BEN format.
+//! [`decode_ben_to_jsonl`] then unpacks a BEN file's bit-packed runs back
+//! into assignment vectors.
+
+use std::io::{self, Read, Write};
+
+use flate2::read::DeflateDecoder;
+use xz2::read::XzDecoder;
+
+use crate::encode::{decode_ben_vec, encode_ben_vec_from_rle, fse, XbenCodec, XBEN_VERSION};
+
+const MAGIC: &[u8; 17] = b"STANDARD BEN FILE";
+
+/// Inverts [`crate::encode::jsonl_encode_xben_with_codec`] /
+/// [`crate::encode::encode_ben_to_xben_with_codec`], turning an XBEN file
+/// back into a BEN file.
+///
+/// Reads the `STANDARD BEN FILE` magic and the `(tag, level, version)`
+/// header triplet, dispatches decompression to whichever backend
+/// `tag`/`level` name, then repacks the decompressed ben32 run stream
+/// into the bit-packed BEN format one line at a time.
+///
+/// A header naming a version other than [`XBEN_VERSION`] is rejected
+/// outright rather than parsed speculatively: the header layout has
+/// changed across versions (an extra `level` byte was inserted before
+/// the version byte in version 3), so guessing at an unsupported layout
+/// would risk silently misinterpreting the file instead of failing
+/// loudly, which is the whole reason the version byte exists.
+pub fn decode_xben_to_ben<R: Read, W: Write>(mut reader: R, mut writer: W) -> io::Result<()> {
+    let mut magic = [0u8; 17];
+    reader.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(invalid("Not an XBEN file: missing STANDARD BEN FILE magic"));
+    }
+
+    let mut header = [0u8; 3];
+    reader.read_exact(&mut header)?;
+    let [tag, level, version] = header;
+
+    if version != XBEN_VERSION {
+        return Err(invalid(&format!(
+            "Unsupported XBEN container version {version} (this build reads version {XBEN_VERSION}); re-encode the file with a matching ben version"
+        )));
+    }
+
+    let codec = XbenCodec::from_header(tag, level)?;
+
+    let mut ben32 = Vec::new();
+    match codec {
+        XbenCodec::Lzma2 { .. } => {
+            XzDecoder::new(reader).read_to_end(&mut ben32)?;
+        }
+        XbenCodec::Deflate { .. } => {
+            DeflateDecoder::new(reader).read_to_end(&mut ben32)?;
+        }
+        XbenCodec::Fse => {
+            let mut compressed = Vec::new();
+            reader.read_to_end(&mut compressed)?;
+            ben32 = fse::fse_decompress_ben32(&compressed)?;
+        }
+    }
+
+    ben32_to_ben(&ben32, &mut writer)
+}
+
+/// Splits a ben32 byte stream (one or more lines, each a sequence of
+/// 8-byte big-endian `(district: u32, run_length: u32)` codes terminated
+/// by the all-zero sentinel code) back into the bit-packed BEN format,
+/// writing the `STANDARD BEN FILE` magic followed by each line's
+/// bit-packed runs.
+fn ben32_to_ben<W: Write>(ben32: &[u8], writer: &mut W) -> io::Result<()> {
+    if ben32.len() % 8 != 0 {
+        return Err(invalid("ben32 stream length must be a multiple of 8 bytes"));
+    }
+
+    writer.write_all(MAGIC)?;
+
+    let mut rle_vec: Vec<(u32, u32)> = Vec::new();
+    for code in ben32.chunks_exact(8) {
+        let value = u32::from_be_bytes(code[0..4].try_into().unwrap());
+        let length = u32::from_be_bytes(code[4..8].try_into().unwrap());
+        if value == 0 && length == 0 {
+            if !rle_vec.is_empty() {
+                writer.write_all(&encode_ben_vec_from_rle(std::mem::take(&mut rle_vec)))?;
+            }
+            continue;
+        }
+        rle_vec.push((value, length));
+    }
+
+    Ok(())
+}
+
+/// Decodes a BEN file back into JSONL, one `{"assignment": [...]}` line
+/// per BEN line.
+///
+/// `n_units` is the length of every assignment vector in the file (the
+/// number of graph units being districted). The BEN format itself only
+/// records how many bytes each line's bit-packed body occupies, not how
+/// many units it unpacks to, so the caller must supply it — the same
+/// value that was used to build the assignment vectors `jsonl_encode_ben`
+/// originally read.
+pub fn decode_ben_to_jsonl<R: Read, W: Write>(
+    mut reader: R,
+    mut writer: W,
+    n_units: usize,
+) -> io::Result<()> {
+    let mut magic = [0u8; 17];
+    reader.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(invalid("Not a BEN file: missing STANDARD BEN FILE magic"));
+    }
+
+    let mut body = Vec::new();
+    reader.read_to_end(&mut body)?;
+
+    let mut pos = 0;
+    while pos < body.len() {
+        let (assignments, consumed) = decode_ben_vec(&body[pos..], n_units)?;
+        pos += consumed;
+        let line = serde_json::json!({ "assignment": assignments });
+        writeln!(writer, "{line}")?;
+    }
+
+    Ok(())
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    include!("tests/decode_tests.rs");
+}
@@ -0,0 +1,143 @@
+//! ASCII-armored BEN/XBEN container: a base64 encoding of a BEN or XBEN
+//! byte stream, wrapped in a short textual banner so the armored form is
+//! self-identifying and safe to paste into JSON payloads, logs, or any
+//! other text-only channel.
+//!
+//! Both the armor and de-armor paths are streaming: they process the
+//! underlying BEN/XBEN bytes in fixed-size chunks rather than buffering
+//! the whole file, so armoring a very large ensemble doesn't need to
+//! hold it all in memory at once.
+//!
+//! Only library entry points are provided: [`armor_encode`] and
+//! [`armor_decode`] are not wired to any command-line flag.
+
+use std::io::{self, BufRead, Read, Write};
+
+const BEGIN_BANNER: &str = "-----BEGIN BEN ARMORED FILE-----";
+const END_BANNER: &str = "-----END BEN ARMORED FILE-----";
+
+/// Number of raw bytes base64-encoded per output line. 48 bytes maps to
+/// 64 base64 characters, the same wrapping width RFC 4648 / PEM use.
+const CHUNK_BYTES: usize = 48;
+
+const ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Armors a BEN or XBEN byte stream, writing a `BEGIN`/`END` banner
+/// around fixed-width, line-wrapped base64.
+pub fn armor_encode<R: Read, W: Write>(mut reader: R, mut writer: W) -> io::Result<()> {
+    writeln!(writer, "{BEGIN_BANNER}")?;
+
+    let mut buf = [0u8; CHUNK_BYTES];
+    loop {
+        let n = read_fill(&mut reader, &mut buf)?;
+        if n == 0 {
+            break;
+        }
+
+        let mut line = String::new();
+        encode_chunk(&buf[..n], &mut line);
+        writeln!(writer, "{line}")?;
+
+        if n < CHUNK_BYTES {
+            break;
+        }
+    }
+
+    writeln!(writer, "{END_BANNER}")?;
+    Ok(())
+}
+
+/// Inverts [`armor_encode`], stripping the banner and decoding the
+/// base64 body back into the original BEN/XBEN bytes.
+pub fn armor_decode<R: Read, W: Write>(reader: R, mut writer: W) -> io::Result<()> {
+    for line in io::BufReader::new(reader).lines() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() || line == BEGIN_BANNER || line == END_BANNER {
+            continue;
+        }
+        writer.write_all(&decode_line(line)?)?;
+    }
+    Ok(())
+}
+
+/// Reads until `buf` is full or the reader is exhausted, returning the
+/// number of bytes actually filled (a short final read is not an error,
+/// it is simply the last, partial chunk).
+fn read_fill<R: Read>(reader: &mut R, buf: &mut [u8]) -> io::Result<usize> {
+    let mut total = 0;
+    while total < buf.len() {
+        let n = reader.read(&mut buf[total..])?;
+        if n == 0 {
+            break;
+        }
+        total += n;
+    }
+    Ok(total)
+}
+
+fn encode_chunk(chunk: &[u8], out: &mut String) {
+    for group in chunk.chunks(3) {
+        let b1 = group.get(1).copied();
+        let b2 = group.get(2).copied();
+        let n = (group[0] as u32) << 16 | (b1.unwrap_or(0) as u32) << 8 | b2.unwrap_or(0) as u32;
+
+        out.push(ALPHABET[(n >> 18 & 0x3F) as usize] as char);
+        out.push(ALPHABET[(n >> 12 & 0x3F) as usize] as char);
+        out.push(if b1.is_some() {
+            ALPHABET[(n >> 6 & 0x3F) as usize] as char
+        } else {
+            '='
+        });
+        out.push(if b2.is_some() {
+            ALPHABET[(n & 0x3F) as usize] as char
+        } else {
+            '='
+        });
+    }
+}
+
+fn decode_line(line: &str) -> io::Result<Vec<u8>> {
+    let bytes = line.as_bytes();
+    if bytes.len() % 4 != 0 {
+        return Err(invalid("Armored line length is not a multiple of 4"));
+    }
+
+    let mut out = Vec::with_capacity(bytes.len() / 4 * 3);
+    for group in bytes.chunks(4) {
+        let v0 = decode_symbol(group[0]).ok_or_else(|| invalid("Invalid base64 symbol"))?;
+        let v1 = decode_symbol(group[1]).ok_or_else(|| invalid("Invalid base64 symbol"))?;
+        let n = (v0 as u32) << 18 | (v1 as u32) << 12;
+        out.push((n >> 16) as u8);
+
+        if let Some(v2) = decode_symbol(group[2]) {
+            let n = n | (v2 as u32) << 6;
+            out.push((n >> 8) as u8);
+            if let Some(v3) = decode_symbol(group[3]) {
+                out.push((n | v3 as u32) as u8);
+            }
+        }
+    }
+    Ok(out)
+}
+
+fn decode_symbol(b: u8) -> Option<u8> {
+    match b {
+        b'A'..=b'Z' => Some(b - b'A'),
+        b'a'..=b'z' => Some(b - b'a' + 26),
+        b'0'..=b'9' => Some(b - b'0' + 52),
+        b'+' => Some(62),
+        b'/' => Some(63),
+        _ => None,
+    }
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    include!("tests/armor_tests.rs");
+}
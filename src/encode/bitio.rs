@@ -0,0 +1,109 @@
+//! A small bit-level reader/writer used to pack variable-width unsigned
+//! integers into a byte stream.
+//!
+//! Both ends agree on one convention: bits are packed most-significant-bit
+//! first within each field, fields are packed back-to-back with no
+//! padding, and a final partial byte is zero-padded on the low end. This
+//! replaces the hand-rolled shift/mask loops that used to be duplicated
+//! wherever BEN packed or unpacked bit fields, so the one tricky edge
+//! case (the trailing partial byte) only has to be gotten right once.
+
+/// Packs unsigned integers of up to 32 bits into a byte buffer.
+pub struct BitWriter {
+    bytes: Vec<u8>,
+    acc: u64,
+    nbits: u32,
+}
+
+impl BitWriter {
+    pub fn new() -> Self {
+        Self {
+            bytes: Vec::new(),
+            acc: 0,
+            nbits: 0,
+        }
+    }
+
+    /// Writes the low `bits` bits of `value`, most-significant bit
+    /// first. `bits` must be in `0..=32`; `0` is a no-op.
+    pub fn write(&mut self, value: u32, bits: u8) {
+        debug_assert!(bits <= 32);
+        if bits == 0 {
+            return;
+        }
+        self.acc = (self.acc << bits) | (value as u64 & mask(bits));
+        self.nbits += bits as u32;
+        while self.nbits >= 8 {
+            self.nbits -= 8;
+            self.bytes.push((self.acc >> self.nbits) as u8);
+        }
+    }
+
+    /// Flushes any partial trailing byte, zero-padded on the low end,
+    /// and returns the packed bytes.
+    pub fn finish(mut self) -> Vec<u8> {
+        if self.nbits > 0 {
+            let pad = 8 - self.nbits;
+            self.bytes.push(((self.acc << pad) & 0xFF) as u8);
+        }
+        self.bytes
+    }
+}
+
+impl Default for BitWriter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Reads unsigned integers of up to 32 bits back out of a byte slice
+/// written by [`BitWriter`].
+pub struct BitReader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    acc: u64,
+    nbits: u32,
+}
+
+impl<'a> BitReader<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self {
+            bytes,
+            pos: 0,
+            acc: 0,
+            nbits: 0,
+        }
+    }
+
+    /// Reads `bits` bits (`0..=32`), most-significant bit first. Once
+    /// the underlying slice is exhausted, missing bytes read as zero,
+    /// matching the zero-padding [`BitWriter::finish`] applies to its
+    /// final byte.
+    pub fn read(&mut self, bits: u8) -> u32 {
+        debug_assert!(bits <= 32);
+        if bits == 0 {
+            return 0;
+        }
+        while self.nbits < bits as u32 {
+            let byte = self.bytes.get(self.pos).copied().unwrap_or(0);
+            self.pos += 1;
+            self.acc = (self.acc << 8) | byte as u64;
+            self.nbits += 8;
+        }
+        self.nbits -= bits as u32;
+        ((self.acc >> self.nbits) & mask(bits)) as u32
+    }
+}
+
+fn mask(bits: u8) -> u64 {
+    if bits >= 64 {
+        u64::MAX
+    } else {
+        (1u64 << bits) - 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    include!("tests/bitio_tests.rs");
+}
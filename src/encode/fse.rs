@@ -0,0 +1,531 @@
+//! Finite State Entropy (tANS) codec for the ben32 run stream.
+//!
+//! This is an alternative backend to LZMA2 for XBEN files. Instead of
+//! dictionary compression on the raw ben32 bytes, it splits each 8-byte
+//! ben32 run code (a big-endian `u32` district label followed by a
+//! big-endian `u32` run length) into four 16-bit symbol streams — the
+//! district label's high and low halves, then the run length's high and
+//! low halves — and entropy-codes each stream independently with its own
+//! tANS table. Ensembles where the same handful of district labels and
+//! run lengths recur compress almost as well as LZMA2 this way, and
+//! decode substantially faster since there is no dictionary to walk.
+//!
+//! Each symbol stream is processed in fixed-size blocks so the table can
+//! adapt to local statistics; every block carries its own normalized
+//! frequency table in its header, so blocks can be decoded independently
+//! given the header.
+//!
+//! The algorithm follows the usual tANS construction: normalize symbol
+//! counts to sum to a power-of-two table size `L`, spread symbols across
+//! the `L` states, then derive per-state decode transitions (and, on the
+//! encode side, the inverse per-symbol transitions) from that spread.
+//! Encoding walks a block in reverse maintaining a single `state` value,
+//! emitting a variable number of low bits per symbol; decoding walks
+//! forward from the final state recorded in the header, looking up the
+//! symbol and the next state directly from the table.
+
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::io::{self, Read, Write};
+
+/// Default table-size exponent (`L = 2^TABLE_LOG`). Widened per block (up
+/// to 16) if a block has more distinct symbols than this table can hold.
+const TABLE_LOG: u32 = 12;
+
+/// Number of symbols entropy-coded per block. Chosen to be large enough
+/// that the table-header overhead is negligible, but small enough that
+/// the table can still track local statistics in a long ensemble.
+const BLOCK_SYMBOLS: usize = 1 << 15;
+
+/// A single row of the decode table: being in state `state` means "emit
+/// `symbol`, read `nb_bits` bits `b` from the stream, and transition to
+/// `base_state + b`".
+struct DecodeEntry {
+    symbol: u16,
+    nb_bits: u8,
+    base_state: u32,
+}
+
+/// One candidate transition available when encoding a given symbol. Rows
+/// for a symbol are sorted by `new_state` and together partition the
+/// entire `[0, table_size)` state space, so exactly one row matches
+/// whatever state the encoder is currently in.
+struct EncodeEntry {
+    new_state: u32,
+    nb_bits: u8,
+    pos: u32,
+}
+
+fn floor_log2(v: u32) -> u32 {
+    31 - v.leading_zeros()
+}
+
+/// Picks a table-size exponent large enough to give every distinct symbol
+/// in the block at least one slot, capped at 16 bits.
+fn choose_table_log(n_distinct: usize) -> u32 {
+    let mut log = TABLE_LOG;
+    while ((1u32 << log) as usize) < n_distinct && log < 16 {
+        log += 1;
+    }
+    log
+}
+
+/// Scales raw symbol counts so they sum to exactly `2^table_log`, with
+/// every symbol that appears at all guaranteed at least one slot.
+fn normalize_counts(freqs: &HashMap<u16, u32>, table_log: u32) -> Vec<(u16, u32)> {
+    let table_size = 1u64 << table_log;
+    let total: u64 = freqs.values().map(|&c| c as u64).sum();
+
+    let mut symbols: Vec<u16> = freqs.keys().copied().collect();
+    symbols.sort_unstable();
+
+    let mut norm: Vec<(u16, u32)> = symbols
+        .into_iter()
+        .map(|sym| {
+            let c = freqs[&sym] as u64;
+            let scaled = ((c * table_size) / total).max(1) as u32;
+            (sym, scaled)
+        })
+        .collect();
+
+    let mut diff = table_size as i64 - norm.iter().map(|&(_, n)| n as i64).sum::<i64>();
+
+    // Spread the rounding error across the most frequent symbols first,
+    // never dropping a present symbol below a single slot.
+    norm.sort_by_key(|&(_, n)| std::cmp::Reverse(n));
+    let mut i = 0;
+    while diff != 0 {
+        let idx = i % norm.len();
+        if diff > 0 {
+            norm[idx].1 += 1;
+            diff -= 1;
+        } else if norm[idx].1 > 1 {
+            norm[idx].1 -= 1;
+            diff += 1;
+        }
+        i += 1;
+    }
+
+    norm.sort_by_key(|&(sym, _)| sym);
+    norm
+}
+
+/// Spreads each symbol's normalized count across the `L`-state table
+/// using the standard FSE step (odd, so coprime with, any power-of-two
+/// `L` of 16 or more), so that occurrences of the same symbol land
+/// roughly evenly spaced.
+fn build_spread_table(norm: &[(u16, u32)], table_log: u32) -> Vec<u16> {
+    let table_size = 1usize << table_log;
+    let mut table = vec![0u16; table_size];
+    let step = (table_size >> 1) + (table_size >> 3) + 3;
+    let mask = table_size - 1;
+
+    let mut pos = 0usize;
+    for &(sym, count) in norm {
+        for _ in 0..count {
+            table[pos] = sym;
+            pos = (pos + step) & mask;
+        }
+    }
+    table
+}
+
+/// Groups table positions by symbol, in ascending state order. This is
+/// the order decode-table construction assigns ranks in, so index `i`
+/// here lines up with rank `i` there.
+fn build_rank_positions(spread: &[u16]) -> HashMap<u16, Vec<u32>> {
+    let mut positions: HashMap<u16, Vec<u32>> = HashMap::new();
+    for (pos, &sym) in spread.iter().enumerate() {
+        positions.entry(sym).or_default().push(pos as u32);
+    }
+    positions
+}
+
+fn build_decode_table(spread: &[u16], norm: &[(u16, u32)], table_log: u32) -> Vec<DecodeEntry> {
+    let table_size = spread.len() as u32;
+    let mut next_rank: HashMap<u16, u32> = norm.iter().copied().collect();
+
+    spread
+        .iter()
+        .map(|&sym| {
+            let rank = next_rank.get_mut(&sym).unwrap();
+            let v = *rank;
+            *rank += 1;
+
+            let nb_bits = (table_log - floor_log2(v)) as u8;
+            let base_state = (v << nb_bits) - table_size;
+            DecodeEntry {
+                symbol: sym,
+                nb_bits,
+                base_state,
+            }
+        })
+        .collect()
+}
+
+/// Builds, for every symbol present, the sorted list of transitions used
+/// while encoding. This is the exact inverse of `build_decode_table`: the
+/// row built for rank `i` (`v = count + i`) and the decode-table row at
+/// `positions[sym][i]` describe the same `(symbol, nb_bits, state)`
+/// transition. Rows are then re-sorted by `new_state`, so a row's index
+/// in the returned list is not its rank.
+fn build_encode_rows(
+    norm: &[(u16, u32)],
+    positions: &HashMap<u16, Vec<u32>>,
+    table_log: u32,
+    table_size: u32,
+) -> HashMap<u16, Vec<EncodeEntry>> {
+    let mut rows: HashMap<u16, Vec<EncodeEntry>> = HashMap::new();
+    for &(sym, count) in norm {
+        let pos_list = &positions[&sym];
+        let mut entries: Vec<EncodeEntry> = (0..count)
+            .map(|i| {
+                let v = count + i;
+                let nb_bits = (table_log - floor_log2(v)) as u8;
+                let new_state = (v << nb_bits) - table_size;
+                EncodeEntry {
+                    new_state,
+                    nb_bits,
+                    pos: pos_list[i as usize],
+                }
+            })
+            .collect();
+        entries.sort_by_key(|e| e.new_state);
+        rows.insert(sym, entries);
+    }
+    rows
+}
+
+/// Accumulates bits MSB-first into a byte buffer.
+struct BitSink {
+    bytes: Vec<u8>,
+    acc: u64,
+    nbits: u32,
+}
+
+impl BitSink {
+    fn new() -> Self {
+        Self {
+            bytes: Vec::new(),
+            acc: 0,
+            nbits: 0,
+        }
+    }
+
+    fn push(&mut self, value: u32, bits: u8) {
+        if bits == 0 {
+            return;
+        }
+        self.acc = (self.acc << bits) | (value as u64 & ((1u64 << bits) - 1));
+        self.nbits += bits as u32;
+        while self.nbits >= 8 {
+            self.nbits -= 8;
+            self.bytes.push((self.acc >> self.nbits) as u8);
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.nbits > 0 {
+            let pad = 8 - self.nbits;
+            self.bytes.push(((self.acc << pad) & 0xFF) as u8);
+        }
+        self.bytes
+    }
+}
+
+/// Reads bits MSB-first from a byte slice, as written by `BitSink`.
+struct BitSource<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    acc: u64,
+    nbits: u32,
+}
+
+impl<'a> BitSource<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self {
+            bytes,
+            pos: 0,
+            acc: 0,
+            nbits: 0,
+        }
+    }
+
+    fn read(&mut self, bits: u8) -> u32 {
+        if bits == 0 {
+            return 0;
+        }
+        while self.nbits < bits as u32 {
+            let byte = self.bytes.get(self.pos).copied().unwrap_or(0);
+            self.pos += 1;
+            self.acc = (self.acc << 8) | byte as u64;
+            self.nbits += 8;
+        }
+        self.nbits -= bits as u32;
+        ((self.acc >> self.nbits) & ((1u64 << bits) - 1)) as u32
+    }
+}
+
+fn encode_block(block: &[u16], out: &mut Vec<u8>) {
+    let mut freqs: HashMap<u16, u32> = HashMap::new();
+    for &s in block {
+        *freqs.entry(s).or_insert(0) += 1;
+    }
+
+    let table_log = choose_table_log(freqs.len());
+    let table_size = 1u32 << table_log;
+    let norm = normalize_counts(&freqs, table_log);
+    let spread = build_spread_table(&norm, table_log);
+    let positions = build_rank_positions(&spread);
+    let rows = build_encode_rows(&norm, &positions, table_log, table_size);
+
+    // Walk the block in reverse, recording each symbol's emitted bits.
+    // They come out last-symbol-first and must be reversed before
+    // packing so a forward reader consumes them first-symbol-first.
+    let mut emitted: Vec<(u32, u8)> = Vec::with_capacity(block.len());
+    let mut state: u32 = 0;
+    for &sym in block.iter().rev() {
+        let symbol_rows = &rows[&sym];
+        let idx = symbol_rows.partition_point(|r| r.new_state <= state) - 1;
+        let row = &symbol_rows[idx];
+        emitted.push((state - row.new_state, row.nb_bits));
+        state = row.pos;
+    }
+    let final_state = state;
+
+    let mut sink = BitSink::new();
+    for &(value, bits) in emitted.iter().rev() {
+        sink.push(value, bits);
+    }
+    let payload = sink.finish();
+
+    out.extend((block.len() as u32).to_be_bytes());
+    out.push(table_log as u8);
+    out.extend(final_state.to_be_bytes());
+    out.extend((norm.len() as u16).to_be_bytes());
+    for &(sym, count) in &norm {
+        out.extend(sym.to_be_bytes());
+        out.extend(count.to_be_bytes());
+    }
+    out.extend((payload.len() as u32).to_be_bytes());
+    out.extend(payload);
+}
+
+/// A corrupted or truncated FSE stream, reported instead of panicking
+/// since this decoder must handle untrusted input.
+fn truncated() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        "truncated or corrupted FSE stream",
+    )
+}
+
+fn read_u8(data: &[u8], pos: usize) -> io::Result<u8> {
+    data.get(pos).copied().ok_or_else(truncated)
+}
+
+fn read_u16(data: &[u8], pos: usize) -> io::Result<u16> {
+    data.get(pos..pos + 2)
+        .and_then(|b| b.try_into().ok())
+        .map(u16::from_be_bytes)
+        .ok_or_else(truncated)
+}
+
+fn read_u32(data: &[u8], pos: usize) -> io::Result<u32> {
+    data.get(pos..pos + 4)
+        .and_then(|b| b.try_into().ok())
+        .map(u32::from_be_bytes)
+        .ok_or_else(truncated)
+}
+
+fn read_u64(data: &[u8], pos: usize) -> io::Result<u64> {
+    data.get(pos..pos + 8)
+        .and_then(|b| b.try_into().ok())
+        .map(u64::from_be_bytes)
+        .ok_or_else(truncated)
+}
+
+fn read_slice(data: &[u8], pos: usize, len: usize) -> io::Result<&[u8]> {
+    data.get(pos..pos + len).ok_or_else(truncated)
+}
+
+fn rest(data: &[u8], pos: usize) -> io::Result<&[u8]> {
+    data.get(pos..).ok_or_else(truncated)
+}
+
+fn decode_block(data: &[u8]) -> io::Result<(Vec<u16>, usize)> {
+    let mut pos = 0;
+
+    let n = read_u32(data, pos)? as usize;
+    pos += 4;
+    let table_log = read_u8(data, pos)? as u32;
+    pos += 1;
+    if table_log == 0 || table_log > 16 {
+        return Err(truncated());
+    }
+    let table_size = 1usize << table_log;
+
+    let final_state = read_u32(data, pos)?;
+    pos += 4;
+    let n_distinct = read_u16(data, pos)? as usize;
+    pos += 2;
+
+    let mut norm = Vec::with_capacity(n_distinct);
+    let mut total_count: usize = 0;
+    for _ in 0..n_distinct {
+        let sym = read_u16(data, pos)?;
+        pos += 2;
+        let count = read_u32(data, pos)? as usize;
+        pos += 4;
+        if count == 0 || count > table_size {
+            return Err(truncated());
+        }
+        total_count += count;
+        if total_count > table_size {
+            return Err(truncated());
+        }
+        norm.push((sym, count as u32));
+    }
+    if total_count != table_size {
+        return Err(truncated());
+    }
+
+    let payload_len = read_u32(data, pos)? as usize;
+    pos += 4;
+    let payload = read_slice(data, pos, payload_len)?;
+    pos += payload_len;
+
+    if final_state as usize >= table_size {
+        return Err(truncated());
+    }
+
+    let spread = build_spread_table(&norm, table_log);
+    let positions = build_rank_positions(&spread);
+    if norm
+        .iter()
+        .any(|&(sym, count)| positions.get(&sym).map_or(0, Vec::len) != count as usize)
+    {
+        return Err(truncated());
+    }
+    let decode_table = build_decode_table(&spread, &norm, table_log);
+
+    let mut source = BitSource::new(payload);
+    let mut state = final_state;
+    let mut symbols = Vec::with_capacity(n.min(BLOCK_SYMBOLS));
+    for _ in 0..n {
+        let entry = decode_table.get(state as usize).ok_or_else(truncated)?;
+        symbols.push(entry.symbol);
+        let b = source.read(entry.nb_bits);
+        state = entry.base_state + b;
+    }
+
+    Ok((symbols, pos))
+}
+
+fn encode_stream(symbols: &[u16], out: &mut Vec<u8>) {
+    out.extend((symbols.len() as u64).to_be_bytes());
+    for block in symbols.chunks(BLOCK_SYMBOLS) {
+        encode_block(block, out);
+    }
+}
+
+fn decode_stream(data: &[u8]) -> io::Result<(Vec<u16>, usize)> {
+    let total = read_u64(data, 0)? as usize;
+    let mut pos = 8;
+    let mut out = Vec::with_capacity(total.min(BLOCK_SYMBOLS));
+    while out.len() < total {
+        let (symbols, consumed) = decode_block(rest(data, pos)?)?;
+        pos += consumed;
+        out.extend(symbols);
+    }
+    Ok((out, pos))
+}
+
+/// Entropy-codes a ben32 byte stream (a sequence of 8-byte big-endian
+/// `(district: u32, run_length: u32)` codes, as produced by
+/// [`Ben32Scratch::encode_line`](super::Ben32Scratch)) using FSE.
+///
+/// The district label and run length are each split into high/low 16-bit
+/// halves and coded as four independent symbol streams, since the four
+/// halves tend to follow very different distributions.
+pub fn fse_compress_ben32(ben32: &[u8]) -> io::Result<Vec<u8>> {
+    if ben32.len() % 8 != 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "ben32 stream length must be a multiple of 8 bytes",
+        ));
+    }
+
+    let n_codes = ben32.len() / 8;
+    let mut value_hi = Vec::with_capacity(n_codes);
+    let mut value_lo = Vec::with_capacity(n_codes);
+    let mut len_hi = Vec::with_capacity(n_codes);
+    let mut len_lo = Vec::with_capacity(n_codes);
+    for code in ben32.chunks_exact(8) {
+        let value = u32::from_be_bytes(code[0..4].try_into().unwrap());
+        let length = u32::from_be_bytes(code[4..8].try_into().unwrap());
+        value_hi.push((value >> 16) as u16);
+        value_lo.push((value & 0xFFFF) as u16);
+        len_hi.push((length >> 16) as u16);
+        len_lo.push((length & 0xFFFF) as u16);
+    }
+
+    let mut out = Vec::new();
+    encode_stream(&value_hi, &mut out);
+    encode_stream(&value_lo, &mut out);
+    encode_stream(&len_hi, &mut out);
+    encode_stream(&len_lo, &mut out);
+    Ok(out)
+}
+
+/// Inverts [`fse_compress_ben32`], reconstructing the original ben32 byte
+/// stream.
+pub fn fse_decompress_ben32(data: &[u8]) -> io::Result<Vec<u8>> {
+    let (value_hi, consumed) = decode_stream(data)?;
+    let pos = consumed;
+    let (value_lo, consumed) = decode_stream(rest(data, pos)?)?;
+    let pos = pos + consumed;
+    let (len_hi, consumed) = decode_stream(rest(data, pos)?)?;
+    let pos = pos + consumed;
+    let (len_lo, _) = decode_stream(rest(data, pos)?)?;
+
+    if value_hi.len() != value_lo.len()
+        || value_hi.len() != len_hi.len()
+        || value_hi.len() != len_lo.len()
+    {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "FSE district/run-length streams have mismatched lengths",
+        ));
+    }
+
+    let mut out = Vec::with_capacity(value_hi.len() * 8);
+    for i in 0..value_hi.len() {
+        let value = ((value_hi[i] as u32) << 16) | value_lo[i] as u32;
+        let length = ((len_hi[i] as u32) << 16) | len_lo[i] as u32;
+        out.extend(value.to_be_bytes());
+        out.extend(length.to_be_bytes());
+    }
+    Ok(out)
+}
+
+/// Convenience wrapper that runs [`fse_compress_ben32`]/
+/// [`fse_decompress_ben32`] over a full reader/writer pair, for callers
+/// that already have the whole ben32 stream in memory or buffered.
+pub fn fse_compress<R: Read, W: Write>(mut reader: R, mut writer: W) -> io::Result<()> {
+    let mut ben32 = Vec::new();
+    reader.read_to_end(&mut ben32)?;
+    writer.write_all(&fse_compress_ben32(&ben32)?)
+}
+
+/// See [`fse_compress`].
+pub fn fse_decompress<R: Read, W: Write>(mut reader: R, mut writer: W) -> io::Result<()> {
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data)?;
+    writer.write_all(&fse_decompress_ben32(&data)?)
+}
+
+#[cfg(test)]
+mod tests {
+    include!("tests/fse_tests.rs");
+}
@@ -15,69 +15,185 @@
 //! bytes to read for each sample.
 //!
 //!
-//! The XBEN format uses LZMA2 dictionary compression on
-//! a byte-level decompressed version of the BEN format (known as ben32)
-//! to achieve better compression ratios than we could achieve with applying
-//! LZMA2 compression directly to the BEN format.
-
+//! The XBEN format compresses a byte-level decompressed version of the
+//! BEN format (known as ben32) to achieve better compression ratios than
+//! we could achieve with applying compression directly to the BEN
+//! format. Several codecs are available, each trading ratio for decode
+//! speed differently; see [`XbenCodec`].
+
+pub mod armor;
+mod bitio;
+pub mod fse;
 pub mod relabel;
 pub mod translate;
 
 use crate::utils::*;
+use bytemuck::cast_slice;
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
 use serde_json::Value;
 use std::io::{self, BufRead, Write};
 use xz2::write::XzEncoder;
 
+use self::bitio::{BitReader, BitWriter};
 use self::translate::ben_to_ben32_lines;
 
-/// This function takes a json encoded line containing an assignment
-/// vector and a sample number and encodes the assignment vector
-/// into a binary format known as "ben32". The ben32 format serves
-/// as an intermediate format that allows for efficient compression
-/// of BEN files using LZMA2 compression methods.
-///
-/// # Arguments
-///
-/// * `data` - A JSON object containing an assignment vector and a sample number
-///
-/// # Returns
-///
-/// A vector of bytes containing the ben32 encoded assignment vector
-fn encode_ben_32_line(data: Value) -> Vec<u8> {
-    let assign_vec = data["assignment"].as_array().unwrap();
-    let mut prev_assign: u16 = 0;
-    let mut count: u16 = 0;
-    let mut first = true;
-
-    let mut ret = Vec::new();
-
-    for assignment in assign_vec {
-        let assign = assignment.as_u64().unwrap() as u16;
-        if first {
-            prev_assign = assign;
-            count = 1;
-            first = false;
-            continue;
+/// Identifies which backend compresses the ben32 stream inside an XBEN
+/// container, and at what level. Written as a plaintext `(tag, level)`
+/// byte pair right after the `STANDARD BEN FILE` magic so that a decoder
+/// can dispatch without having to guess or attempt multiple backends.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum XbenCodec {
+    /// LZMA2 (via `xz2`) applied to the raw ben32 byte stream. This is
+    /// the original XBEN backend and remains the default. `level`
+    /// ranges `0..=9`; higher compresses more but is slower both ways.
+    Lzma2 { level: u32 },
+    /// Finite State Entropy coding of the ben32 run stream, split into
+    /// district-label and run-length symbol streams. See [`fse`]. Has no
+    /// adjustable level.
+    Fse,
+    /// Streaming DEFLATE (via `flate2`), for callers who want a much
+    /// faster round trip than LZMA2 and can accept a worse ratio.
+    /// `level` ranges `0..=9`.
+    Deflate { level: u32 },
+}
+
+impl Default for XbenCodec {
+    fn default() -> Self {
+        XbenCodec::Lzma2 { level: 9 }
+    }
+}
+
+impl XbenCodec {
+    fn tag(self) -> u8 {
+        match self {
+            XbenCodec::Lzma2 { .. } => 0,
+            XbenCodec::Fse => 1,
+            XbenCodec::Deflate { .. } => 2,
         }
-        if assign == prev_assign {
-            count += 1;
-        } else {
-            let encoded = (prev_assign as u32) << 16 | count as u32;
-            ret.extend(&encoded.to_be_bytes());
-            // Reset for next run
-            prev_assign = assign;
-            count = 1;
+    }
+
+    fn level(self) -> u32 {
+        match self {
+            XbenCodec::Lzma2 { level } | XbenCodec::Deflate { level } => level,
+            XbenCodec::Fse => 0,
         }
     }
 
-    // Handle the last run
-    if count > 0 {
-        let encoded = (prev_assign as u32) << 16 | count as u32;
-        ret.extend(&encoded.to_be_bytes());
+    /// Parses the `(tag, level)` byte pair written at the front of an
+    /// XBEN file. Used by the decode side to dispatch to the matching
+    /// decompressor.
+    pub fn from_header(tag: u8, level: u8) -> io::Result<Self> {
+        match tag {
+            0 => Ok(XbenCodec::Lzma2 {
+                level: level as u32,
+            }),
+            1 => Ok(XbenCodec::Fse),
+            2 => Ok(XbenCodec::Deflate {
+                level: level as u32,
+            }),
+            other => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Unknown XBEN codec byte: {other}"),
+            )),
+        }
     }
+}
 
-    ret.extend([0, 0, 0, 0]);
-    ret
+/// The XBEN container version written by this crate. Bumped whenever the
+/// plaintext header layout changes, so that a decoder can at least fail
+/// loudly instead of silently misinterpreting a newer file.
+///
+/// Version 3 added the codec `level` byte and, since the codec tag makes
+/// it redundant, stopped also writing the `STANDARD BEN FILE` magic a
+/// second time inside the compressed payload.
+pub(crate) const XBEN_VERSION: u8 = 3;
+
+/// Number of JSONL lines parsed and ben32-encoded together by one call to
+/// [`encode_ben_32_lines_batch`]. Large enough that the [`Ben32Scratch`]
+/// reuse it enables pays off; small enough to keep memory bounded when
+/// lines describe very wide assignment vectors.
+const BATCH_LINES: usize = 1024;
+
+/// Scratch buffers reused across many lines by [`encode_ben_32_lines_batch`]
+/// so that a batch only allocates its assignment and run-code buffers
+/// once instead of once per line.
+#[derive(Default)]
+struct Ben32Scratch {
+    assignments: Vec<u32>,
+    codes: Vec<u32>,
+}
+
+impl Ben32Scratch {
+    /// Appends the ben32 encoding of `data["assignment"]` to `out`.
+    ///
+    /// `self.assignments` and `self.codes` are cleared (not reallocated)
+    /// at the start of each call. Equal-run boundaries are found with a
+    /// single [`slice::chunk_by`] scan instead of a hand-rolled scalar
+    /// loop, and each run is packed as a `(value, length)` pair of `u32`s
+    /// into `self.codes` and reinterpreted as big-endian bytes in one
+    /// [`cast_slice`] call instead of extending `out` a field at a time.
+    ///
+    /// Both the district label and the run length are full `u32`s — a
+    /// run longer than `u32::MAX` is split into multiple consecutive
+    /// codes for the same value, the same way [`assign_to_rle`] splits an
+    /// oversized run for the plain BEN path, so neither field is ever
+    /// truncated the way packing them into 16 bits each would truncate
+    /// them.
+    fn encode_line(&mut self, data: &Value, out: &mut Vec<u8>) {
+        self.assignments.clear();
+        self.assignments.extend(
+            data["assignment"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|v| v.as_u64().unwrap() as u32),
+        );
+
+        self.codes.clear();
+        for run in self.assignments.chunk_by(|a, b| a == b) {
+            let value = run[0];
+            let mut remaining = run.len() as u64;
+            while remaining > 0 {
+                let chunk = remaining.min(u32::MAX as u64) as u32;
+                self.codes.push(value.to_be());
+                self.codes.push(chunk.to_be());
+                remaining -= chunk as u64;
+            }
+        }
+        out.extend_from_slice(cast_slice(&self.codes));
+        out.extend([0u8; 8]);
+    }
+}
+
+/// Parses and ben32-encodes a batch of JSONL lines, writing each line's
+/// runs straight into `writer`. Reusing one [`Ben32Scratch`] and one
+/// output buffer across the whole batch amortizes the per-line
+/// allocations that encoding each line separately would otherwise pay
+/// for. Each line is still parsed on its own with `serde_json::from_str`;
+/// the JSON parse is not shared across the batch.
+fn encode_ben_32_lines_batch<W: Write>(lines: &[String], writer: &mut W) -> io::Result<()> {
+    let mut scratch = Ben32Scratch::default();
+    let mut out = Vec::new();
+    for line in lines {
+        let data: Value = serde_json::from_str(line).expect("Error parsing JSON from line");
+        out.clear();
+        scratch.encode_line(&data, &mut out);
+        writer.write_all(&out)?;
+    }
+    Ok(())
+}
+
+/// Pulls up to [`BATCH_LINES`] lines from `lines` into `batch` (cleared
+/// first), returning whether any lines were read. Used by
+/// [`jsonl_encode_xben_with_codec`] to feed [`encode_ben_32_lines_batch`]
+/// without reading the whole file into memory at once.
+fn fill_batch<R: BufRead>(lines: &mut io::Lines<R>, batch: &mut Vec<String>) -> io::Result<bool> {
+    batch.clear();
+    for line_result in lines.take(BATCH_LINES) {
+        batch.push(line_result?);
+    }
+    Ok(!batch.is_empty())
 }
 
 /// This function takes a JSONL file and compresses it to the
@@ -95,24 +211,61 @@ fn encode_ben_32_line(data: Value) -> Vec<u8> {
 /// the byte level to achieve better compression ratios. In order
 /// to use XBEN files, the `decode_xben_to_ben` function must be
 /// used to decode the file back into a BEN format.
-pub fn jsonl_encode_xben<R: BufRead, W: Write>(reader: R, mut writer: W) -> std::io::Result<()> {
-    let mut buffer: Vec<u8> = Vec::new();
-    let mut encoder = XzEncoder::new(&mut buffer, 9);
-
-    let mut line_num = 1;
-
-    encoder.write_all("STANDARD BEN FILE".as_bytes())?;
-    for line_result in reader.lines() {
-        print!("Encoding line: {}\r", line_num);
-        line_num += 1;
-        let line = line_result?;
-        let data: Value = serde_json::from_str(&line).expect("Error parsing JSON from line");
+pub fn jsonl_encode_xben<R: BufRead, W: Write>(reader: R, writer: W) -> std::io::Result<()> {
+    jsonl_encode_xben_with_codec(reader, writer, XbenCodec::default())
+}
 
-        let ben32_vec = encode_ben_32_line(data);
-        encoder.write_all(&ben32_vec)?;
+/// Same as [`jsonl_encode_xben`], but lets the caller pick which codec
+/// (and, where applicable, level) compresses the ben32 stream. The
+/// chosen codec is written as a plaintext byte pair right after the
+/// `STANDARD BEN FILE` magic.
+///
+/// The LZMA2 and DEFLATE backends stream compressed bytes straight into
+/// `writer` as each line is encoded; only the FSE backend needs the full
+/// ben32 stream in memory first, since it writes each of its four symbol
+/// streams whole, one after another.
+pub fn jsonl_encode_xben_with_codec<R: BufRead, W: Write>(
+    reader: R,
+    mut writer: W,
+    codec: XbenCodec,
+) -> std::io::Result<()> {
+    let mut line_num: usize = 0;
+    writer.write_all(b"STANDARD BEN FILE")?;
+    writer.write_all(&[codec.tag(), codec.level() as u8, XBEN_VERSION])?;
+
+    let mut lines = reader.lines();
+    let mut batch: Vec<String> = Vec::with_capacity(BATCH_LINES);
+
+    match codec {
+        XbenCodec::Lzma2 { level } => {
+            let mut encoder = XzEncoder::new(&mut writer, level);
+            while fill_batch(&mut lines, &mut batch)? {
+                line_num += batch.len();
+                print!("Encoding line: {}\r", line_num);
+                encode_ben_32_lines_batch(&batch, &mut encoder)?;
+            }
+            drop(encoder); // Make sure to flush and finish compression
+        }
+        XbenCodec::Deflate { level } => {
+            let mut encoder = DeflateEncoder::new(&mut writer, Compression::new(level));
+            while fill_batch(&mut lines, &mut batch)? {
+                line_num += batch.len();
+                print!("Encoding line: {}\r", line_num);
+                encode_ben_32_lines_batch(&batch, &mut encoder)?;
+            }
+            drop(encoder); // Make sure to flush and finish compression
+        }
+        XbenCodec::Fse => {
+            let mut ben32: Vec<u8> = Vec::new();
+            while fill_batch(&mut lines, &mut batch)? {
+                line_num += batch.len();
+                print!("Encoding line: {}\r", line_num);
+                encode_ben_32_lines_batch(&batch, &mut ben32)?;
+            }
+            writer.write_all(&fse::fse_compress_ben32(&ben32)?)?;
+        }
     }
-    drop(encoder); // Make sure to flush and finish compression
-    writer.write_all(&buffer)?;
+
     eprintln!();
     eprintln!("Done!");
     Ok(())
@@ -162,6 +315,11 @@ pub fn xz_compress<R: BufRead, W: Write>(mut reader: R, writer: W) -> std::io::R
 /// This function takes a run-length encoded assignment vector and
 /// encodes into a bit-packed ben version
 ///
+/// `max_val_bits` and `max_len_bits` are computed as the true variable
+/// widths needed to hold the largest value and run length present (up
+/// to 32 bits each), so district labels and run lengths are never
+/// truncated the way a fixed `u16` field would truncate them.
+///
 /// # Arguments
 ///
 /// * `rle_vec` - A vector of tuples containing the value and length of each run
@@ -169,13 +327,13 @@ pub fn xz_compress<R: BufRead, W: Write>(mut reader: R, writer: W) -> std::io::R
 /// # Returns
 ///
 /// A vector of bytes containing the bit-packed ben encoded assignment vector
-fn encode_ben_vec_from_rle(rle_vec: Vec<(u16, u16)>) -> Vec<u8> {
+pub(crate) fn encode_ben_vec_from_rle(rle_vec: Vec<(u32, u32)>) -> Vec<u8> {
     let mut output_vec: Vec<u8> = Vec::new();
 
-    let max_val: u16 = rle_vec.iter().max_by_key(|x| x.0).unwrap().0;
-    let max_len: u16 = rle_vec.iter().max_by_key(|x| x.1).unwrap().1;
-    let max_val_bits: u8 = (16 - max_val.leading_zeros() as u8).max(1);
-    let max_len_bits: u8 = 16 - max_len.leading_zeros() as u8;
+    let max_val: u32 = rle_vec.iter().max_by_key(|x| x.0).unwrap().0;
+    let max_len: u32 = rle_vec.iter().max_by_key(|x| x.1).unwrap().1;
+    let max_val_bits: u8 = (32 - max_val.leading_zeros() as u8).max(1);
+    let max_len_bits: u8 = (32 - max_len.leading_zeros() as u8).max(1);
     let assign_bits: u32 = (max_val_bits + max_len_bits) as u32;
     let n_bytes: u32 = if (assign_bits * rle_vec.len() as u32) % 8 == 0 {
         (assign_bits * rle_vec.len() as u32) / 8
@@ -187,43 +345,52 @@ fn encode_ben_vec_from_rle(rle_vec: Vec<(u16, u16)>) -> Vec<u8> {
     output_vec.push(max_len_bits);
     output_vec.extend(n_bytes.to_be_bytes().as_slice());
 
-    let mut remainder: u32 = 0;
-    let mut remainder_bits: u8 = 0;
-
+    let mut writer = BitWriter::new();
     for (val, len) in rle_vec {
-        let mut new_val: u32 = (remainder << max_val_bits) | (val as u32);
-
-        let mut buff: u8;
-
-        let mut n_bits_left: u8 = remainder_bits + max_val_bits;
-
-        while n_bits_left >= 8 {
-            n_bits_left -= 8;
-            buff = (new_val >> n_bits_left) as u8;
-            output_vec.push(buff);
-            new_val = new_val & (!((0xFFFFFFFF as u32) << n_bits_left));
-        }
-
-        new_val = (new_val << max_len_bits) | (len as u32);
-        n_bits_left += max_len_bits;
-
-        while n_bits_left >= 8 {
-            n_bits_left -= 8;
-            buff = (new_val >> n_bits_left) as u8;
-            output_vec.push(buff);
-            new_val = new_val & (!((0xFFFFFFFF as u32) << n_bits_left));
-        }
-
-        remainder_bits = n_bits_left;
-        remainder = new_val;
+        writer.write(val, max_val_bits);
+        writer.write(len, max_len_bits);
     }
+    output_vec.extend(writer.finish());
 
-    if remainder_bits > 0 {
-        let buff = (remainder << (8 - remainder_bits)) as u8;
-        output_vec.push(buff);
+    output_vec
+}
+
+/// Inverts [`encode_ben_vec_from_rle`], unpacking one BEN line's
+/// `(max_val_bits, max_len_bits, n_bytes, packed body)` header and body
+/// back into a flat assignment vector of `n_units` district labels.
+///
+/// `n_units` must be supplied by the caller: the BEN format records how
+/// many bytes a line's bit-packed body occupies, not how many runs (or
+/// units) it unpacks to, the same way [`encode_ben_vec_from_rle`] is
+/// handed a complete assignment vector rather than writing its length
+/// out itself.
+///
+/// # Returns
+///
+/// The decoded assignment vector, and the number of bytes of `data`
+/// consumed (the 6-byte header plus the packed body), so the caller can
+/// advance to the next line.
+pub(crate) fn decode_ben_vec(data: &[u8], n_units: usize) -> io::Result<(Vec<u32>, usize)> {
+    let header = data.get(..6).ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidData, "truncated BEN line header")
+    })?;
+    let max_val_bits = header[0];
+    let max_len_bits = header[1];
+    let n_bytes = u32::from_be_bytes(header[2..6].try_into().unwrap()) as usize;
+    let body = data.get(6..6 + n_bytes).ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidData, "truncated BEN line body")
+    })?;
+
+    let mut reader = BitReader::new(body);
+    let mut assignments = Vec::with_capacity(n_units);
+    while assignments.len() < n_units {
+        let val = reader.read(max_val_bits);
+        let len = reader.read(max_len_bits) as usize;
+        let remaining = n_units - assignments.len();
+        assignments.extend(std::iter::repeat(val).take(len.min(remaining)));
     }
 
-    output_vec
+    Ok((assignments, 6 + n_bytes))
 }
 
 /// This function takes a JSONL file and compresses it into
@@ -281,10 +448,10 @@ pub fn jsonl_encode_ben<R: BufRead, W: Write>(reader: R, mut writer: W) -> std::
         let data: Value = serde_json::from_str(&line).expect("Error parsing JSON from line");
 
         if let Some(assign_vec) = data["assignment"].as_array() {
-            let rle_vec: Vec<(u16, u16)> = assign_to_rle(
+            let rle_vec: Vec<(u32, u32)> = assign_to_rle(
                 assign_vec
                     .into_iter()
-                    .map(|x| x.as_u64().unwrap() as u16)
+                    .map(|x| x.as_u64().unwrap() as u32)
                     .collect(),
             );
 
@@ -308,9 +475,18 @@ pub fn jsonl_encode_ben<R: BufRead, W: Write>(reader: R, mut writer: W) -> std::
 /// # Returns
 ///
 /// A Result type that contains the result of the operation
-pub fn encode_ben_to_xben<R: BufRead, W: Write>(
+pub fn encode_ben_to_xben<R: BufRead, W: Write>(reader: R, writer: W) -> std::io::Result<()> {
+    encode_ben_to_xben_with_codec(reader, writer, XbenCodec::default())
+}
+
+/// Same as [`encode_ben_to_xben`], but lets the caller pick which codec
+/// (and, where applicable, level) compresses the ben32 stream. See
+/// [`jsonl_encode_xben_with_codec`] for the container layout this writes
+/// and which codecs stream rather than buffer.
+pub fn encode_ben_to_xben_with_codec<R: BufRead, W: Write>(
     mut reader: R,
     mut writer: W,
+    codec: XbenCodec,
 ) -> std::io::Result<()> {
     let mut check_buffer = [0u8; 17];
     reader.read_exact(&mut check_buffer)?;
@@ -322,15 +498,26 @@ pub fn encode_ben_to_xben<R: BufRead, W: Write>(
         ));
     }
 
-    let mut buffer: Vec<u8> = Vec::new();
-    let mut encoder = XzEncoder::new(&mut buffer, 9);
-
-    encoder.write_all(b"STANDARD BEN FILE")?;
+    writer.write_all(b"STANDARD BEN FILE")?;
+    writer.write_all(&[codec.tag(), codec.level() as u8, XBEN_VERSION])?;
 
-    ben_to_ben32_lines(reader, &mut encoder)?;
-
-    drop(encoder); // Make sure to flush and finish compression
-    writer.write_all(&buffer)?;
+    match codec {
+        XbenCodec::Lzma2 { level } => {
+            let mut encoder = XzEncoder::new(&mut writer, level);
+            ben_to_ben32_lines(reader, &mut encoder)?;
+            drop(encoder); // Make sure to flush and finish compression
+        }
+        XbenCodec::Deflate { level } => {
+            let mut encoder = DeflateEncoder::new(&mut writer, Compression::new(level));
+            ben_to_ben32_lines(reader, &mut encoder)?;
+            drop(encoder); // Make sure to flush and finish compression
+        }
+        XbenCodec::Fse => {
+            let mut ben32: Vec<u8> = Vec::new();
+            ben_to_ben32_lines(reader, &mut ben32)?;
+            writer.write_all(&fse::fse_compress_ben32(&ben32)?)?;
+        }
+    }
 
     Ok(())
 }
@@ -0,0 +1,37 @@
+use super::*;
+
+fn round_trip(data: &[u8]) -> Vec<u8> {
+    let mut armored = Vec::new();
+    armor_encode(data, &mut armored).unwrap();
+
+    let mut decoded = Vec::new();
+    armor_decode(armored.as_slice(), &mut decoded).unwrap();
+    decoded
+}
+
+#[test]
+fn round_trips_empty_input() {
+    assert_eq!(round_trip(&[]), Vec::<u8>::new());
+}
+
+#[test]
+fn round_trips_a_single_chunk() {
+    let data: Vec<u8> = (0..20).collect();
+    assert_eq!(round_trip(&data), data);
+}
+
+#[test]
+fn round_trips_multiple_chunks() {
+    let data: Vec<u8> = (0..250).map(|i| (i * 7) as u8).collect();
+    assert_eq!(round_trip(&data), data);
+}
+
+#[test]
+fn wraps_banner_around_the_body() {
+    let mut armored = Vec::new();
+    armor_encode(b"STANDARD BEN FILE".as_slice(), &mut armored).unwrap();
+    let text = String::from_utf8(armored).unwrap();
+
+    assert!(text.starts_with(BEGIN_BANNER));
+    assert!(text.trim_end().ends_with(END_BANNER));
+}
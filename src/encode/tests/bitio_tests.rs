@@ -0,0 +1,43 @@
+use super::*;
+
+#[test]
+fn round_trips_mixed_widths() {
+    let mut writer = BitWriter::new();
+    writer.write(0b101, 3);
+    writer.write(0xFFFF_FFFF, 32);
+    writer.write(0, 1);
+    writer.write(42, 8);
+    let bytes = writer.finish();
+
+    let mut reader = BitReader::new(&bytes);
+    assert_eq!(reader.read(3), 0b101);
+    assert_eq!(reader.read(32), 0xFFFF_FFFF);
+    assert_eq!(reader.read(1), 0);
+    assert_eq!(reader.read(8), 42);
+}
+
+#[test]
+fn zero_width_fields_are_no_ops() {
+    let mut writer = BitWriter::new();
+    writer.write(7, 3);
+    writer.write(999, 0);
+    writer.write(2, 2);
+    let bytes = writer.finish();
+
+    let mut reader = BitReader::new(&bytes);
+    assert_eq!(reader.read(3), 7);
+    assert_eq!(reader.read(0), 0);
+    assert_eq!(reader.read(2), 2);
+}
+
+#[test]
+fn pads_missing_trailing_bits_with_zero() {
+    let mut writer = BitWriter::new();
+    writer.write(1, 1);
+    let bytes = writer.finish();
+    assert_eq!(bytes.len(), 1);
+
+    let mut reader = BitReader::new(&bytes);
+    assert_eq!(reader.read(1), 1);
+    assert_eq!(reader.read(7), 0);
+}
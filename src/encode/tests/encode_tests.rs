@@ -0,0 +1,43 @@
+use super::*;
+use crate::decode::decode_xben_to_ben;
+use crate::utils::assign_to_rle;
+
+fn make_ben(lines: &[Vec<u32>]) -> Vec<u8> {
+    let mut out = Vec::new();
+    out.extend_from_slice(b"STANDARD BEN FILE");
+    for assignment in lines {
+        out.extend(encode_ben_vec_from_rle(assign_to_rle(assignment.clone())));
+    }
+    out
+}
+
+#[test]
+fn decode_ben_vec_inverts_encode_ben_vec_from_rle() {
+    let assignments = vec![1, 1, 1, 2, 2, 3, 3, 3, 3, 1];
+    let rle_vec = assign_to_rle(assignments.clone());
+    let encoded = encode_ben_vec_from_rle(rle_vec);
+
+    let (decoded, consumed) = decode_ben_vec(&encoded, assignments.len()).unwrap();
+
+    assert_eq!(decoded, assignments);
+    assert_eq!(consumed, encoded.len());
+}
+
+#[test]
+fn ben_to_xben_to_ben_round_trips_for_every_codec() {
+    let ben = make_ben(&[vec![1, 1, 1, 2, 2, 3], vec![4, 4, 5, 5, 5]]);
+
+    for codec in [
+        XbenCodec::Lzma2 { level: 1 },
+        XbenCodec::Deflate { level: 1 },
+        XbenCodec::Fse,
+    ] {
+        let mut xben = Vec::new();
+        encode_ben_to_xben_with_codec(ben.as_slice(), &mut xben, codec).unwrap();
+
+        let mut decoded = Vec::new();
+        decode_xben_to_ben(xben.as_slice(), &mut decoded).unwrap();
+
+        assert_eq!(decoded, ben, "round trip failed for {codec:?}");
+    }
+}
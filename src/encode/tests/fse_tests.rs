@@ -0,0 +1,85 @@
+use super::*;
+
+fn make_ben32(codes: &[(u32, u32)]) -> Vec<u8> {
+    let mut out = Vec::new();
+    for &(district, len) in codes {
+        out.extend(district.to_be_bytes());
+        out.extend(len.to_be_bytes());
+    }
+    out
+}
+
+#[test]
+fn round_trips_a_small_stream() {
+    let codes = [(1, 3), (2, 1), (1, 7), (1, 7), (3, 2), (2, 1), (0, 0)];
+    let ben32 = make_ben32(&codes);
+
+    let compressed = fse_compress_ben32(&ben32).unwrap();
+    let decompressed = fse_decompress_ben32(&compressed).unwrap();
+
+    assert_eq!(decompressed, ben32);
+}
+
+#[test]
+fn round_trips_a_single_symbol_stream() {
+    let codes = [(5, 10); 20];
+    let ben32 = make_ben32(&codes);
+
+    let compressed = fse_compress_ben32(&ben32).unwrap();
+    let decompressed = fse_decompress_ben32(&compressed).unwrap();
+
+    assert_eq!(decompressed, ben32);
+}
+
+#[test]
+fn round_trips_across_a_block_boundary() {
+    let mut codes = Vec::new();
+    for i in 0..(BLOCK_SYMBOLS + 17) {
+        codes.push(((i % 5) as u32, ((i % 11) + 1) as u32));
+    }
+    let ben32 = make_ben32(&codes);
+
+    let compressed = fse_compress_ben32(&ben32).unwrap();
+    let decompressed = fse_decompress_ben32(&compressed).unwrap();
+
+    assert_eq!(decompressed, ben32);
+}
+
+#[test]
+fn round_trips_a_district_label_above_u16_max() {
+    let codes = [(70_000, 3), (2, 1), (100_000, 7), (3, 2)];
+    let ben32 = make_ben32(&codes);
+
+    let compressed = fse_compress_ben32(&ben32).unwrap();
+    let decompressed = fse_decompress_ben32(&compressed).unwrap();
+
+    assert_eq!(decompressed, ben32);
+}
+
+#[test]
+fn rejects_a_truncated_stream_instead_of_panicking() {
+    let codes = [(1, 3), (2, 1), (1, 7), (3, 2)];
+    let ben32 = make_ben32(&codes);
+    let compressed = fse_compress_ben32(&ben32).unwrap();
+
+    let truncated = &compressed[..compressed.len() - 2];
+    assert!(fse_decompress_ben32(truncated).is_err());
+}
+
+#[test]
+fn rejects_a_symbol_count_that_overruns_the_table_instead_of_spinning() {
+    // A hand-crafted block: table_log=1 (table_size=2), one symbol whose
+    // claimed count is wildly larger than the table could ever hold.
+    // build_spread_table must never be reached with this, or it would
+    // spin for as long as the attacker-chosen count says to.
+    let mut block = Vec::new();
+    block.extend(1u32.to_be_bytes()); // n
+    block.push(1); // table_log
+    block.extend(0u32.to_be_bytes()); // final_state
+    block.extend(1u16.to_be_bytes()); // n_distinct
+    block.extend(0u16.to_be_bytes()); // symbol
+    block.extend(u32::MAX.to_be_bytes()); // count, far beyond table_size
+    block.extend(0u32.to_be_bytes()); // payload_len
+
+    assert!(decode_block(&block).is_err());
+}
@@ -0,0 +1,33 @@
+use super::*;
+use crate::encode::encode_ben_vec_from_rle;
+use crate::utils::assign_to_rle;
+
+#[test]
+fn decode_ben_to_jsonl_round_trips_multiple_lines() {
+    let lines = [vec![1, 1, 1, 2, 2, 3], vec![4, 4, 4, 5, 5, 1]];
+    let n_units = lines[0].len();
+
+    let mut ben = Vec::new();
+    ben.extend_from_slice(b"STANDARD BEN FILE");
+    for assignment in &lines {
+        ben.extend(encode_ben_vec_from_rle(assign_to_rle(assignment.clone())));
+    }
+
+    let mut out = Vec::new();
+    decode_ben_to_jsonl(ben.as_slice(), &mut out, n_units).unwrap();
+
+    let text = String::from_utf8(out).unwrap();
+    let decoded: Vec<Vec<u32>> = text
+        .lines()
+        .map(|line| {
+            serde_json::from_str::<serde_json::Value>(line).unwrap()["assignment"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|v| v.as_u64().unwrap() as u32)
+                .collect()
+        })
+        .collect();
+
+    assert_eq!(decoded, lines);
+}
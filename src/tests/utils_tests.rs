@@ -0,0 +1,22 @@
+use super::*;
+
+#[test]
+fn merges_consecutive_runs() {
+    let assign = vec![1, 1, 1, 2, 2, 3, 1, 1];
+    assert_eq!(
+        assign_to_rle(assign),
+        vec![(1, 3), (2, 2), (3, 1), (1, 2)]
+    );
+}
+
+#[test]
+fn handles_empty_input() {
+    assert_eq!(assign_to_rle(Vec::new()), Vec::new());
+}
+
+#[test]
+fn push_run_splits_runs_longer_than_u32_max() {
+    let mut rle_vec = Vec::new();
+    push_run(&mut rle_vec, 5, u32::MAX as u64 + 3);
+    assert_eq!(rle_vec, vec![(5, u32::MAX), (5, 3)]);
+}
@@ -0,0 +1,51 @@
+//! Shared helper functions used across the BEN/XBEN encode and decode
+//! paths.
+
+/// Converts a flat assignment vector into a run-length encoded vector of
+/// `(value, length)` pairs, merging consecutive equal assignments into a
+/// single run.
+///
+/// # Arguments
+///
+/// * `assign_vec` - A vector of district assignments
+///
+/// # Returns
+///
+/// A vector of `(value, length)` tuples describing each run. A run
+/// longer than `u32::MAX` is split into multiple consecutive tuples with
+/// the same value so that no length information is lost.
+pub fn assign_to_rle(assign_vec: Vec<u32>) -> Vec<(u32, u32)> {
+    let mut rle_vec = Vec::new();
+    let mut iter = assign_vec.into_iter();
+
+    let Some(mut prev) = iter.next() else {
+        return rle_vec;
+    };
+    let mut count: u64 = 1;
+
+    for val in iter {
+        if val == prev {
+            count += 1;
+        } else {
+            push_run(&mut rle_vec, prev, count);
+            prev = val;
+            count = 1;
+        }
+    }
+    push_run(&mut rle_vec, prev, count);
+
+    rle_vec
+}
+
+fn push_run(rle_vec: &mut Vec<(u32, u32)>, value: u32, mut count: u64) {
+    while count > u32::MAX as u64 {
+        rle_vec.push((value, u32::MAX));
+        count -= u32::MAX as u64;
+    }
+    rle_vec.push((value, count as u32));
+}
+
+#[cfg(test)]
+mod tests {
+    include!("tests/utils_tests.rs");
+}